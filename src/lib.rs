@@ -1,7 +1,6 @@
 #![no_std]
 #![feature(allocator_api)]
 #![feature(ptr_internals)]
-#![feature(try_reserve)]
 #![feature(dropck_eyepatch)]
 #![feature(rustc_private)]
 
@@ -9,16 +8,26 @@ extern crate alloc;
 use alloc::alloc::handle_alloc_error;
 use alloc::alloc::{Global, Layout};
 use alloc::boxed::Box;
-use core::alloc::Alloc;
+use core::alloc::{AllocError, Allocator, GlobalAlloc};
+use core::cmp;
 use core::mem;
+use core::mem::MaybeUninit;
 use core::ptr::{NonNull, Unique};
 
-pub struct RealBox<T, A: Alloc = Global> {
+pub struct RealBox<T, A: Allocator = Global> {
     ptr: Unique<T>,
+    cap: usize,
     a: A,
 }
 
-impl<T, A: Alloc> RealBox<T, A> {
+/// Panics on a capacity that overflows the address space. Mirrors the
+/// `capacity_overflow` helper in the standard `RawVec`: there is no valid
+/// `Layout` to hand `handle_alloc_error`, so panic rather than abort.
+fn capacity_overflow() -> ! {
+    panic!("capacity overflow")
+}
+
+impl<T, A: Allocator> RealBox<T, A> {
     /// Gets a raw pointer to the start of the allocation. Note that this is
     /// Unique::empty() if `cap = 0` or T is zero-sized. In the former case, you must
     /// be careful.
@@ -36,27 +45,32 @@ impl<T, A: Alloc> RealBox<T, A> {
         &mut self.a
     }
 
+    /// Returns the number of `T` elements backing this allocation. A
+    /// single-element `RealBox` reports `1`.
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
     fn current_layout(&self) -> Option<Layout> {
-        unsafe {
-            let align = mem::align_of::<T>();
-            let size = mem::size_of::<T>();
-            Some(Layout::from_size_align_unchecked(size, align))
+        if mem::size_of::<T>() == 0 || self.cap == 0 {
+            None
+        } else {
+            // The allocation is `cap` contiguous `T`s, so its layout is the
+            // array layout rather than a single `size_of::<T>()`.
+            Layout::array::<T>(self.cap).ok()
         }
     }
 }
 
-impl<T, A: Alloc> RealBox<T, A> {
+impl<T, A: Allocator> RealBox<T, A> {
     pub unsafe fn dealloc_buffer(&mut self) {
-        let elem_size = mem::size_of::<T>();
-        if elem_size != 0 {
-            if let Some(layout) = self.current_layout() {
-                self.a.dealloc(NonNull::from(self.ptr).cast(), layout);
-            }
+        if let Some(layout) = self.current_layout() {
+            self.a.deallocate(NonNull::from(self.ptr).cast(), layout);
         }
     }
 }
 
-unsafe impl<#[may_dangle] T, A: Alloc> Drop for RealBox<T, A> {
+unsafe impl<#[may_dangle] T, A: Allocator> Drop for RealBox<T, A> {
     fn drop(&mut self) {
         unsafe {
             self.dealloc_buffer();
@@ -64,32 +78,151 @@ unsafe impl<#[may_dangle] T, A: Alloc> Drop for RealBox<T, A> {
     }
 }
 
-impl<T, A: Alloc> RealBox<T, A> {
+impl<T, A: Allocator> RealBox<T, A> {
     pub(crate) fn new_in(a: A) -> Self {
-        RealBox::allocate_in(true, a)
+        RealBox::allocate_in(true, 1, a)
     }
 
-    fn allocate_in(zeroed: bool, mut a: A) -> Self {
+    /// Fallible counterpart of `new_in`: propagates `AllocError` from the
+    /// allocator instead of aborting the process via `handle_alloc_error`.
+    pub(crate) fn try_new_in(a: A) -> Result<Self, AllocError> {
+        RealBox::try_allocate_in(true, 1, a)
+    }
+
+    /// Allocates room for `len` contiguous `T`s, handling the `len == 0` and
+    /// ZST cases the same way the single-element path does.
+    pub fn new_slice_in(len: usize, a: A) -> Self {
+        RealBox::allocate_in(true, len, a)
+    }
+
+    /// Fallible counterpart of `new_slice_in`.
+    pub fn try_new_slice_in(len: usize, a: A) -> Result<Self, AllocError> {
+        RealBox::try_allocate_in(true, len, a)
+    }
+
+    fn allocate_in(zeroed: bool, cap: usize, a: A) -> Self {
+        // The infallible path is the fallible one with an abort on failure, so
+        // the two can never drift apart.
+        Self::try_allocate_in(zeroed, cap, a).unwrap_or_else(|_| match Layout::array::<T>(cap) {
+            Ok(layout) => handle_alloc_error(layout),
+            // The layout itself overflowed, so there is nothing to hand
+            // `handle_alloc_error`.
+            Err(_) => capacity_overflow(),
+        })
+    }
+
+    fn try_allocate_in(zeroed: bool, cap: usize, a: A) -> Result<Self, AllocError> {
         let elem_size = mem::size_of::<T>();
 
         // handles ZSTs and `cap = 0` alike
-        let ptr = if elem_size == 0 {
+        let ptr = if elem_size == 0 || cap == 0 {
             NonNull::<T>::dangling()
         } else {
-            let align = mem::align_of::<T>();
-            let layout = Layout::from_size_align(elem_size, align).unwrap();
+            let layout = Layout::array::<T>(cap).map_err(|_| AllocError)?;
             let result = if zeroed {
-                unsafe { a.alloc_zeroed(layout) }
+                a.allocate_zeroed(layout)
             } else {
-                unsafe { a.alloc(layout) }
+                a.allocate(layout)
             };
-            match result {
-                Ok(ptr) => ptr.cast(),
-                Err(_) => handle_alloc_error(layout),
+            // `Allocator` hands back a `NonNull<[u8]>`; narrow it to the element
+            // pointer.
+            result?.cast()
+        };
+
+        Ok(RealBox {
+            ptr: ptr.into(),
+            cap,
+            a,
+        })
+    }
+}
+
+impl<T, A: Allocator> RealBox<T, A> {
+    /// Ensures room for at least `new_cap` elements, reallocating the backing
+    /// buffer if it is currently smaller. Aborts on allocation failure; see
+    /// `try_reserve` for the fallible variant.
+    pub fn reserve(&mut self, new_cap: usize) {
+        self.try_reserve(new_cap).unwrap_or_else(|_| match Layout::array::<T>(new_cap) {
+            Ok(layout) => handle_alloc_error(layout),
+            Err(_) => capacity_overflow(),
+        })
+    }
+
+    /// Fallible growth: reallocates so the buffer holds at least `new_cap`
+    /// elements, using the classic amortized-doubling strategy so repeated
+    /// pushes stay O(1). Returns `AllocError` on overflow or allocator failure
+    /// instead of aborting.
+    pub fn try_reserve(&mut self, new_cap: usize) -> Result<(), AllocError> {
+        if new_cap <= self.cap {
+            return Ok(());
+        }
+
+        // Grow by at least a factor of two so a sequence of single-element
+        // reservations is amortized O(1).
+        let target = cmp::max(new_cap, self.cap.saturating_mul(2));
+
+        let elem_size = mem::size_of::<T>();
+        if elem_size == 0 {
+            // ZSTs never actually allocate; just record the new capacity.
+            self.cap = target;
+            return Ok(());
+        }
+
+        // Guard against overflowing `usize` when computing the byte size,
+        // bailing to the fallible error path rather than wrapping.
+        let new_size = target.checked_mul(elem_size).ok_or(AllocError)?;
+        let align = mem::align_of::<T>();
+        let new_layout = Layout::from_size_align(new_size, align).map_err(|_| AllocError)?;
+
+        let ptr = unsafe {
+            match self.current_layout() {
+                // `Allocator::grow` already falls back to allocate+copy+
+                // deallocate when the allocator cannot resize in place.
+                Some(cur) => self.a.grow(NonNull::from(self.ptr).cast(), cur, new_layout)?,
+                None => self.a.allocate(new_layout)?,
             }
         };
 
-        RealBox { ptr: ptr.into(), a }
+        self.ptr = Unique::from(ptr.cast());
+        self.cap = target;
+        Ok(())
+    }
+
+    /// Grows the allocation to hold at least `new_cap` elements, returning
+    /// `true` on success and `false` on allocation failure.
+    ///
+    /// The stable `Allocator` trait has no in-place-only primitive like the
+    /// old `Alloc::grow_in_place`, so this wraps `reserve`'s realloc path and
+    /// **may move** the buffer. It is retained for compatibility with the
+    /// growth API introduced alongside `reserve`.
+    pub fn grow_in_place(&mut self, new_cap: usize) -> bool {
+        self.try_reserve(new_cap).is_ok()
+    }
+}
+
+impl<T, A: Allocator> RealBox<MaybeUninit<T>, A> {
+    /// Allocates an uninitialized buffer large enough for a `T`, skipping the
+    /// zero-fill that `new_in` pays for. The caller is responsible for fully
+    /// initializing the value before calling `assume_init`.
+    pub fn new_uninit_in(a: A) -> Self {
+        // `MaybeUninit<T>` shares its layout with `T`, so this reserves exactly
+        // the right amount of space while leaving the contents untouched.
+        RealBox::allocate_in(false, 1, a)
+    }
+
+    /// Converts to `RealBox<T, A>`, asserting that the buffer has been
+    /// initialized.
+    ///
+    /// # Safety
+    ///
+    /// The contents must have been fully initialized; acting on an
+    /// uninitialized `T` is undefined behavior.
+    pub unsafe fn assume_init(self) -> RealBox<T, A> {
+        let ptr = self.ptr.cast();
+        let cap = self.cap;
+        let a = core::ptr::read(&self.a);
+        mem::forget(self);
+        RealBox { ptr, cap, a }
     }
 }
 
@@ -98,6 +231,12 @@ impl<T> RealBox<T, Global> {
         Self::new_in(Global)
     }
 
+    /// Fallible counterpart of `new`, returning `AllocError` on allocation
+    /// failure rather than aborting.
+    pub fn try_new() -> Result<Self, AllocError> {
+        Self::try_new_in(Global)
+    }
+
     /// Converts the entire buffer into `Box<T>`.
     pub unsafe fn into_box(self) -> Box<T> {
         let output: Box<T> = Box::from_raw(self.ptr());
@@ -109,41 +248,107 @@ impl<T> RealBox<T, Global> {
 impl<T> RealBox<T, Global> {
     pub fn heap_init<F>(initialize: F) -> Box<T>
     where
-        F: Fn(&mut T),
+        F: FnOnce(&mut MaybeUninit<T>),
     {
         unsafe {
-            let mut t = Self::new_in(Global).into_box();
-            initialize(t.as_mut());
-            t
+            let uninit = RealBox::<MaybeUninit<T>, Global>::new_uninit_in(Global);
+            // The buffer is uninitialized, so the closure must write *into* a
+            // `MaybeUninit<T>` — handing it a `&mut T` would run drop glue on
+            // garbage when it assigns over a `Drop`-carrying field.
+            initialize(&mut *uninit.ptr());
+            uninit.assume_init().into_box()
+        }
+    }
+
+    /// Fallible counterpart of `heap_init`: propagates `AllocError` from the
+    /// backing allocation instead of aborting before running `initialize`.
+    pub fn try_heap_init<F>(initialize: F) -> Result<Box<T>, AllocError>
+    where
+        F: FnOnce(&mut MaybeUninit<T>),
+    {
+        unsafe {
+            let uninit = RealBox::<MaybeUninit<T>, Global>::try_new_in(Global)?;
+            initialize(&mut *uninit.ptr());
+            Ok(uninit.assume_init().into_box())
         }
     }
 }
 
-impl<T, A: Alloc> RealBox<T, A> {
+impl<T, A: Allocator> RealBox<T, A> {
     pub fn new_with_allocator(a: A) -> Self {
         Self::new_in(a)
     }
 }
 
-impl<T, A: Alloc> RealBox<T, A> {
-    pub unsafe fn from_raw_parts(ptr: *mut T, a: A) -> Self {
+impl<T, A: Allocator> RealBox<T, A> {
+    pub unsafe fn from_raw_parts(ptr: *mut T, cap: usize, a: A) -> Self {
         RealBox {
             ptr: Unique::new_unchecked(ptr),
+            cap,
             a,
         }
     }
 }
 
+impl<T, A: Allocator> RealBox<T, A> {
+    /// Converts the whole `cap`-element allocation into a `Box<[T], A>`,
+    /// handing ownership of the buffer (and allocator) to the box.
+    ///
+    /// # Safety
+    ///
+    /// All `cap` elements must have been initialized and live; the resulting
+    /// `Box` assumes ownership of them and will drop them. The caller must not
+    /// use `self` after this call (ownership of the buffer is relinquished).
+    pub unsafe fn into_boxed_slice(self) -> Box<[T], A> {
+        let slice = core::slice::from_raw_parts_mut(self.ptr(), self.cap);
+        let a = core::ptr::read(&self.a);
+        mem::forget(self);
+        Box::from_raw_in(slice as *mut [T], a)
+    }
+}
+
 impl<T> RealBox<T, Global> {
     pub fn from_box(mut slice: Box<[T]>) -> Self {
         unsafe {
-            let result = RealBox::from_raw_parts(slice.as_mut_ptr(), Global);
+            let len = slice.len();
+            let result = RealBox::from_raw_parts(slice.as_mut_ptr(), len, Global);
             mem::forget(slice);
             result
         }
     }
 }
 
+/// Adapts a `GlobalAlloc` implementation into an `Allocator`, so an existing
+/// `#[global_allocator]`-style type can still back a `RealBox` after the move
+/// off the old `Alloc` trait.
+pub struct GlobalAllocAdapter<G: GlobalAlloc>(pub G);
+
+unsafe impl<G: GlobalAlloc> Allocator for GlobalAllocAdapter<G> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = if layout.size() == 0 {
+            NonNull::new(layout.align() as *mut u8).ok_or(AllocError)?
+        } else {
+            NonNull::new(unsafe { self.0.alloc(layout) }).ok_or(AllocError)?
+        };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = if layout.size() == 0 {
+            NonNull::new(layout.align() as *mut u8).ok_or(AllocError)?
+        } else {
+            NonNull::new(unsafe { self.0.alloc_zeroed(layout) }).ok_or(AllocError)?
+        };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.0.dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::*;
@@ -193,6 +398,48 @@ mod test {
         assert_eq!(ptr, t.ptr.as_ptr());
     }
 
+    #[test]
+    fn test_try_new() {
+        let t = RealBox::<i32>::try_new().unwrap();
+        assert_ne!(t.ptr.as_ptr(), core::ptr::null_mut());
+    }
+
+    #[test]
+    fn test_new_uninit() {
+        let uninit = RealBox::<MaybeUninit<i32>>::new_uninit_in(Global);
+        let t = unsafe {
+            *(uninit.ptr() as *mut i32) = 42;
+            uninit.assume_init()
+        };
+        assert_ne!(t.ptr.as_ptr(), core::ptr::null_mut());
+    }
+
+    #[test]
+    fn test_new_slice() {
+        let b = RealBox::<i32>::new_slice_in(16, Global);
+        assert_eq!(b.cap(), 16);
+        let boxed: Box<[i32]> = unsafe { b.into_boxed_slice() };
+        assert_eq!(boxed.len(), 16);
+    }
+
+    #[test]
+    fn test_reserve_grows() {
+        let mut b = RealBox::<i32>::new_slice_in(0, Global);
+        b.reserve(4);
+        assert!(b.cap() >= 4);
+        // Doubling keeps the growth amortized: a tiny bump past capacity at
+        // least doubles.
+        let cap = b.cap();
+        b.reserve(cap + 1);
+        assert!(b.cap() >= cap * 2);
+    }
+
+    #[test]
+    fn test_reserve_overflow_is_fallible() {
+        let mut b = RealBox::<u64>::new_slice_in(0, Global);
+        assert!(b.try_reserve(usize::MAX).is_err());
+    }
+
     #[test]
     fn test_heap_init() {
         extern crate libc;
@@ -211,10 +458,12 @@ mod test {
             a: [0xff, 0xfe, 0xfd, 0xfc],
         };
 
-        let heap_obj = RealBox::<Obj>::heap_init(|mut t| {
-            t.x = 12;
-            t.y = 0.9;
-            t.a = [0xff, 0xfe, 0xfd, 0xfc]
+        let heap_obj = RealBox::<Obj>::heap_init(|t| {
+            t.write(Obj {
+                x: 12,
+                y: 0.9,
+                a: [0xff, 0xfe, 0xfd, 0xfc],
+            });
         });
 
         let size = mem::size_of::<Obj>();